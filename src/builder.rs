@@ -0,0 +1,66 @@
+use std::io::Result;
+use std::os::unix::io::AsRawFd;
+
+use tokio::io::unix::AsyncFd;
+
+use crate::{set_nonblocking_raw, Anyfd, ShutdownMode};
+
+/// Builder for [`Anyfd`], letting you opt out of the automatic
+/// non-blocking setup and pick a [`ShutdownMode`].
+///
+/// ```no_run
+/// # fn example(fd: std::fs::File) -> std::io::Result<()> {
+/// use anyfd::{Builder, ShutdownMode};
+///
+/// let afd = Builder::new()
+///   .shutdown_mode(ShutdownMode::None)
+///   .build(fd)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Builder {
+  nonblocking: bool,
+  shutdown: ShutdownMode,
+}
+
+impl Builder {
+  /// Start from the same defaults as [`Anyfd::from_fd`]: non-blocking is
+  /// set automatically, and shutdown does `shutdown(SHUT_WR)`.
+  pub fn new() -> Self {
+    Builder {
+      nonblocking: true,
+      shutdown: ShutdownMode::default(),
+    }
+  }
+
+  /// Whether to set `O_NONBLOCK` on the fd when [`build`][Builder::build]
+  /// is called. Defaults to `true`.
+  pub fn nonblocking(mut self, nonblocking: bool) -> Self {
+    self.nonblocking = nonblocking;
+    self
+  }
+
+  /// What [`poll_shutdown`][tokio::io::AsyncWrite::poll_shutdown] should
+  /// do. Defaults to [`ShutdownMode::Socket`]`(`[`std::net::Shutdown::Write`]`)`.
+  pub fn shutdown_mode(mut self, shutdown: ShutdownMode) -> Self {
+    self.shutdown = shutdown;
+    self
+  }
+
+  /// Wrap `fd` according to the configured options.
+  pub fn build<T: AsRawFd>(self, fd: T) -> Result<Anyfd<T>> {
+    if self.nonblocking {
+      set_nonblocking_raw(fd.as_raw_fd())?;
+    }
+    Ok(Anyfd {
+      afd: Some(AsyncFd::new(fd)?),
+      shutdown: self.shutdown,
+    })
+  }
+}
+
+impl Default for Builder {
+  fn default() -> Self {
+    Builder::new()
+  }
+}
@@ -0,0 +1,75 @@
+use std::io::Result;
+use std::os::unix::io::AsRawFd;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::io::{AsyncRead as FuturesAsyncRead, AsyncWrite as FuturesAsyncWrite};
+use futures::ready;
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::Anyfd;
+
+/// Adapts a tokio [`AsyncRead`]/[`AsyncWrite`] type to the `futures-io`
+/// traits used by the broader async ecosystem (smol, async-std, and many
+/// protocol crates), mirroring `tokio-util`'s `Compat`.
+pub struct Compat<I> {
+  inner: I,
+}
+
+impl<T: AsRawFd> Anyfd<T> {
+  /// Wrap this `Anyfd` in a [`Compat`] adapter implementing
+  /// `futures::io::AsyncRead`/`AsyncWrite`.
+  pub fn compat(self) -> Compat<Anyfd<T>> {
+    Compat { inner: self }
+  }
+
+  /// Like [`compat`][Anyfd::compat], but borrows this `Anyfd` instead of
+  /// taking ownership of it.
+  pub fn compat_mut(&mut self) -> Compat<&mut Anyfd<T>> {
+    Compat { inner: self }
+  }
+}
+
+impl<I> Compat<I> {
+  /// Recover the wrapped value.
+  pub fn into_inner(self) -> I {
+    self.inner
+  }
+}
+
+impl<I: AsyncRead + Unpin> FuturesAsyncRead for Compat<I> {
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut [u8]
+  ) -> Poll<Result<usize>> {
+    let mut read_buf = ReadBuf::new(buf);
+    ready!(Pin::new(&mut self.get_mut().inner).poll_read(cx, &mut read_buf))?;
+    Poll::Ready(Ok(read_buf.filled().len()))
+  }
+}
+
+impl<I: AsyncWrite + Unpin> FuturesAsyncWrite for Compat<I> {
+  fn poll_write(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &[u8]
+  ) -> Poll<Result<usize>> {
+    Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+  }
+
+  fn poll_flush(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Result<()>> {
+    Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+  }
+
+  fn poll_close(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Result<()>> {
+    Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+  }
+}
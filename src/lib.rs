@@ -1,7 +1,8 @@
-use std::io::{Result, Error};
+use std::io::{Result, Error, IoSlice, IoSliceMut};
 use std::os::unix::io::AsRawFd;
 
 use std::pin::Pin;
+use std::sync::OnceLock;
 use std::task::{Context, Poll};
 
 use futures::ready;
@@ -10,8 +11,47 @@ use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::io::unix::AsyncFd;
 use tokio::io::ReadBuf;
 
+mod builder;
+mod compat;
+mod pipe;
+mod scm;
+mod split;
+
+pub use builder::Builder;
+pub use compat::Compat;
+pub use pipe::{pipe, PipeReadHalf, PipeReader, PipeWriteHalf, PipeWriter};
+pub use split::{OwnedReadHalf, OwnedWriteHalf, ReuniteError};
+
+/// What [`poll_shutdown`][AsyncWrite::poll_shutdown] does to the
+/// underlying fd, selected via [`Builder::shutdown_mode`].
+#[derive(Clone, Copy)]
+pub enum ShutdownMode {
+  /// Call `shutdown(2)` with the given [`std::net::Shutdown`], as
+  /// appropriate for sockets. If the fd turns out not to be a socket,
+  /// the resulting `ENOTSOCK` is treated as success rather than
+  /// surfaced as an error, so generic pipelines over arbitrary fds
+  /// don't break on shutdown.
+  Socket(std::net::Shutdown),
+  /// Take and close the fd right away, as appropriate for pipes, which
+  /// don't support `shutdown(2)`. On a split half (see
+  /// [`Anyfd::into_split`]) the fd is shared with the other half and
+  /// can't be closed out from under it, so there it degrades to
+  /// [`ShutdownMode::None`].
+  Close,
+  /// Do nothing.
+  None,
+}
+
+impl Default for ShutdownMode {
+  fn default() -> Self {
+    ShutdownMode::Socket(std::net::Shutdown::Write)
+  }
+}
+
 pub struct Anyfd<T: AsRawFd> {
-  afd: AsyncFd<T>,
+  /// `None` once [`ShutdownMode::Close`] has taken and closed the fd.
+  pub(crate) afd: Option<AsyncFd<T>>,
+  pub(crate) shutdown: ShutdownMode,
 }
 
 /// Wrap any suitable file descriptor `fd` as
@@ -19,20 +59,27 @@ pub struct Anyfd<T: AsRawFd> {
 ///
 /// You need to make sure the file descriptor is
 /// non-blocking. Set it with [`set_nonblocking`] if not
-/// already.
+/// already, or use [`Anyfd::from_fd`] to have it done for you.
 ///
 /// [`AsyncRead`]: ../tokio/io/trait.AsyncRead.html
 /// [`AsyncWrite`]: ../tokio/io/trait.AsyncWrite.html
 /// [`set_nonblocking`]: fn.set_nonblocking.html
 pub fn anyfd<T: AsRawFd>(fd: T) -> Result<Anyfd<T>> {
-  Ok(Anyfd { afd: AsyncFd::new(fd)? })
+  Ok(Anyfd { afd: Some(AsyncFd::new(fd)?), shutdown: ShutdownMode::default() })
 }
 
-/// Set `fd` as non-blocking (the [`O_NONBLOCK`] flag).
-///
-/// [`O_NONBLOCK`]: ../libc/constant.O_NONBLOCK.html
-pub fn set_nonblocking(fd: impl AsRawFd) -> Result<()> {
-  let fd = fd.as_raw_fd();
+/// The kernel's limit on iovec count for a single `readv`/`writev` call,
+/// queried once via `sysconf(_SC_IOV_MAX)` and cached, since `libc` has
+/// no `IOV_MAX` constant (it isn't a fixed value on every platform).
+pub(crate) fn iov_max() -> usize {
+  static IOV_MAX: OnceLock<usize> = OnceLock::new();
+  *IOV_MAX.get_or_init(|| {
+    let r = unsafe { libc::sysconf(libc::_SC_IOV_MAX) };
+    if r > 0 { r as usize } else { 1024 }
+  })
+}
+
+pub(crate) fn set_nonblocking_raw(fd: std::os::unix::io::RawFd) -> Result<()> {
   unsafe {
     let mut flags = libc::fcntl(fd, libc::F_GETFL);
     if flags < 0 {
@@ -47,15 +94,87 @@ pub fn set_nonblocking(fd: impl AsRawFd) -> Result<()> {
   Ok(())
 }
 
+/// Set `fd` as non-blocking (the [`O_NONBLOCK`] flag).
+///
+/// [`O_NONBLOCK`]: ../libc/constant.O_NONBLOCK.html
+pub fn set_nonblocking(fd: impl AsRawFd) -> Result<()> {
+  set_nonblocking_raw(fd.as_raw_fd())
+}
+
+impl<T: AsRawFd> Anyfd<T> {
+  /// Wrap `fd`, automatically setting it non-blocking first.
+  ///
+  /// Use [`Builder`] instead if you also need to pick a
+  /// [`ShutdownMode`].
+  pub fn from_fd(fd: T) -> Result<Anyfd<T>> {
+    set_nonblocking_raw(fd.as_raw_fd())?;
+    anyfd(fd)
+  }
+
+  /// Split this `Anyfd` into an owned read half and an owned write half
+  /// that can be used concurrently from separate tasks.
+  ///
+  /// This is only sound for full-duplex file descriptors such as PTYs
+  /// and sockets, where the read half only ever issues read-side
+  /// syscalls and the write half only ever issues write-side syscalls
+  /// on the shared fd. Use [`OwnedReadHalf::reunite`] or
+  /// [`OwnedWriteHalf::reunite`] to recover the original `Anyfd`.
+  pub fn into_split(self) -> (OwnedReadHalf<T>, OwnedWriteHalf<T>) {
+    let afd = self.afd.expect("`Anyfd::into_split`: fd was already closed by `ShutdownMode::Close`");
+    split::split(afd, self.shutdown)
+  }
+
+  /// Like [`poll_read`][AsyncRead::poll_read], but fills several buffers
+  /// at once using `readv(2)`.
+  ///
+  /// `bufs` is clamped to the kernel's iovec count limit, as the kernel
+  /// would reject a longer iovec.
+  pub fn poll_read_vectored(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    bufs: &mut [IoSliceMut<'_>]
+  ) -> Poll<Result<usize>> {
+    let fd = self.fd().as_raw_fd();
+    let n = bufs.len().min(iov_max());
+    let iov: Vec<libc::iovec> = bufs[..n].iter_mut().map(|b| libc::iovec {
+      iov_base: b.as_mut_ptr() as *mut _,
+      iov_len: b.len(),
+    }).collect();
+    loop {
+      let mut guard = ready!(self.fd().poll_read_ready(cx))?;
+
+      match guard.try_io(|_| {
+        let r = unsafe {
+          libc::readv(fd, iov.as_ptr(), iov.len() as libc::c_int)
+        };
+        if r < 0 {
+          Err(Error::last_os_error())
+        } else {
+          Ok(r as usize)
+        }
+      }) {
+        Ok(result) => return Poll::Ready(result),
+        Err(_would_block) => continue,
+      }
+    }
+  }
+
+  /// The wrapped fd, or panics if [`ShutdownMode::Close`] already took
+  /// and closed it.
+  pub(crate) fn fd(&self) -> &AsyncFd<T> {
+    self.afd.as_ref().expect("`Anyfd` used after its fd was closed by `ShutdownMode::Close`")
+  }
+}
+
 impl<T: AsRawFd> AsyncRead for Anyfd<T> {
   fn poll_read(
     self: Pin<&mut Self>,
     cx: &mut Context<'_>,
     buf: &mut ReadBuf<'_>
   ) -> Poll<Result<()>> {
-    let fd = self.afd.as_raw_fd();
+    let fd = self.fd().as_raw_fd();
     loop {
-      let mut guard = ready!(self.afd.poll_read_ready(cx))?;
+      let mut guard = ready!(self.fd().poll_read_ready(cx))?;
 
       match guard.try_io(|_| {
         let r = unsafe {
@@ -84,9 +203,9 @@ impl<T: AsRawFd> AsyncWrite for Anyfd<T> {
     cx: &mut Context<'_>,
     buf: &[u8]
   ) -> Poll<Result<usize>> {
-    let fd = self.afd.as_raw_fd();
+    let fd = self.fd().as_raw_fd();
     loop {
-      let mut guard = ready!(self.afd.poll_write_ready(cx))?;
+      let mut guard = ready!(self.fd().poll_write_ready(cx))?;
 
       match guard.try_io(|_| {
         let r = unsafe {
@@ -105,6 +224,40 @@ impl<T: AsRawFd> AsyncWrite for Anyfd<T> {
     }
   }
 
+  fn poll_write_vectored(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    bufs: &[IoSlice<'_>]
+  ) -> Poll<Result<usize>> {
+    let fd = self.fd().as_raw_fd();
+    let n = bufs.len().min(iov_max());
+    let iov: Vec<libc::iovec> = bufs[..n].iter().map(|b| libc::iovec {
+      iov_base: b.as_ptr() as *mut _,
+      iov_len: b.len(),
+    }).collect();
+    loop {
+      let mut guard = ready!(self.fd().poll_write_ready(cx))?;
+
+      match guard.try_io(|_| {
+        let r = unsafe {
+          libc::writev(fd, iov.as_ptr(), iov.len() as libc::c_int)
+        };
+        if r < 0 {
+          Err(Error::last_os_error())
+        } else {
+          Ok(r as usize)
+        }
+      }) {
+        Ok(result) => return Poll::Ready(result),
+        Err(_would_block) => continue,
+      }
+    }
+  }
+
+  fn is_write_vectored(&self) -> bool {
+    true
+  }
+
   fn poll_flush(
     self: Pin<&mut Self>,
     _cx: &mut Context<'_>,
@@ -116,14 +269,75 @@ impl<T: AsRawFd> AsyncWrite for Anyfd<T> {
     self: Pin<&mut Self>,
     _cx: &mut Context<'_>,
   ) -> Poll<Result<()>> {
-    let fd = self.afd.as_raw_fd();
-    let r = unsafe {
-      libc::shutdown(fd, libc::SHUT_WR)
-    };
-    if r == 0 {
-      Poll::Ready(Ok(()))
-    } else {
-      Poll::Ready(Err(Error::last_os_error()))
+    match self.shutdown {
+      ShutdownMode::Socket(how) => {
+        let how = match how {
+          std::net::Shutdown::Read => libc::SHUT_RD,
+          std::net::Shutdown::Write => libc::SHUT_WR,
+          std::net::Shutdown::Both => libc::SHUT_RDWR,
+        };
+        let fd = self.fd().as_raw_fd();
+        let r = unsafe {
+          libc::shutdown(fd, how)
+        };
+        if r == 0 {
+          Poll::Ready(Ok(()))
+        } else {
+          let err = Error::last_os_error();
+          if err.raw_os_error() == Some(libc::ENOTSOCK) {
+            Poll::Ready(Ok(()))
+          } else {
+            Poll::Ready(Err(err))
+          }
+        }
+      }
+      // This `Anyfd` owns its fd outright, so it's safe to take and
+      // close it right away instead of waiting for it to be dropped.
+      ShutdownMode::Close => {
+        let this = unsafe { self.get_unchecked_mut() };
+        match this.afd.take() {
+          Some(afd) => {
+            let inner = afd.into_inner();
+            let fd = inner.as_raw_fd();
+            let r = unsafe { libc::close(fd) };
+            std::mem::forget(inner);
+            if r == 0 {
+              Poll::Ready(Ok(()))
+            } else {
+              Poll::Ready(Err(Error::last_os_error()))
+            }
+          }
+          None => Poll::Ready(Ok(())),
+        }
+      }
+      ShutdownMode::None => Poll::Ready(Ok(())),
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use std::os::unix::io::FromRawFd;
+
+  use tokio::io::AsyncWriteExt;
+
+  use crate::{Anyfd, PipeWriter};
+
+  #[tokio::test]
+  async fn socket_shutdown_on_non_socket_treats_enotsock_as_ok() {
+    // A pipe fd: pollable like a socket, but not one, so `shutdown(2)`
+    // returns `ENOTSOCK` and `ShutdownMode::Socket` (the default) must
+    // treat that as success rather than surface it.
+    let mut fds = [0; 2];
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let r = unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) };
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    let r = unsafe { libc::pipe(fds.as_mut_ptr()) };
+    assert_eq!(r, 0);
+    unsafe { libc::close(fds[0]) };
+    let writer = unsafe { PipeWriter::from_raw_fd(fds[1]) };
+
+    let mut afd = Anyfd::from_fd(writer).unwrap();
+    afd.shutdown().await.unwrap();
+  }
+}
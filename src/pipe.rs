@@ -0,0 +1,217 @@
+use std::io::{IoSlice, IoSliceMut, Result};
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::unix::AsyncFd;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::{Anyfd, ShutdownMode};
+
+/// The reading end of a pipe created by [`pipe`].
+///
+/// Closes the underlying fd on drop.
+pub struct PipeReader(RawFd);
+
+/// The writing end of a pipe created by [`pipe`].
+///
+/// Closes the underlying fd on drop.
+pub struct PipeWriter(RawFd);
+
+impl AsRawFd for PipeReader {
+  fn as_raw_fd(&self) -> RawFd {
+    self.0
+  }
+}
+
+impl AsRawFd for PipeWriter {
+  fn as_raw_fd(&self) -> RawFd {
+    self.0
+  }
+}
+
+impl FromRawFd for PipeReader {
+  unsafe fn from_raw_fd(fd: RawFd) -> Self {
+    PipeReader(fd)
+  }
+}
+
+impl FromRawFd for PipeWriter {
+  unsafe fn from_raw_fd(fd: RawFd) -> Self {
+    PipeWriter(fd)
+  }
+}
+
+impl IntoRawFd for PipeReader {
+  fn into_raw_fd(self) -> RawFd {
+    let fd = self.0;
+    std::mem::forget(self);
+    fd
+  }
+}
+
+impl IntoRawFd for PipeWriter {
+  fn into_raw_fd(self) -> RawFd {
+    let fd = self.0;
+    std::mem::forget(self);
+    fd
+  }
+}
+
+impl Drop for PipeReader {
+  fn drop(&mut self) {
+    unsafe { libc::close(self.0); }
+  }
+}
+
+impl Drop for PipeWriter {
+  fn drop(&mut self) {
+    unsafe { libc::close(self.0); }
+  }
+}
+
+/// The reading end of a pipe returned by [`pipe`].
+///
+/// Unlike a bare [`Anyfd`], this only implements [`AsyncRead`]: writing
+/// to a pipe's reading end is a compile error here instead of a runtime
+/// `EBADF`.
+pub struct PipeReadHalf(Anyfd<PipeReader>);
+
+/// The writing end of a pipe returned by [`pipe`].
+///
+/// Unlike a bare [`Anyfd`], this only implements [`AsyncWrite`]: reading
+/// from a pipe's writing end is a compile error here instead of a
+/// runtime `EBADF`.
+pub struct PipeWriteHalf(Anyfd<PipeWriter>);
+
+impl PipeReadHalf {
+  /// Like [`Anyfd::poll_read_vectored`].
+  pub fn poll_read_vectored(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    bufs: &mut [IoSliceMut<'_>],
+  ) -> Poll<Result<usize>> {
+    let this = Pin::into_inner(self);
+    Pin::new(&mut this.0).poll_read_vectored(cx, bufs)
+  }
+}
+
+impl AsyncRead for PipeReadHalf {
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut ReadBuf<'_>,
+  ) -> Poll<Result<()>> {
+    let this = Pin::into_inner(self);
+    Pin::new(&mut this.0).poll_read(cx, buf)
+  }
+}
+
+impl AsyncWrite for PipeWriteHalf {
+  fn poll_write(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<Result<usize>> {
+    let this = Pin::into_inner(self);
+    Pin::new(&mut this.0).poll_write(cx, buf)
+  }
+
+  fn poll_write_vectored(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    bufs: &[IoSlice<'_>],
+  ) -> Poll<Result<usize>> {
+    let this = Pin::into_inner(self);
+    Pin::new(&mut this.0).poll_write_vectored(cx, bufs)
+  }
+
+  fn is_write_vectored(&self) -> bool {
+    self.0.is_write_vectored()
+  }
+
+  fn poll_flush(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Result<()>> {
+    let this = Pin::into_inner(self);
+    Pin::new(&mut this.0).poll_flush(cx)
+  }
+
+  fn poll_shutdown(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Result<()>> {
+    let this = Pin::into_inner(self);
+    Pin::new(&mut this.0).poll_shutdown(cx)
+  }
+}
+
+/// Create a non-blocking, tokio-integrated pipe, returning direction-specific
+/// reading and writing ends.
+///
+/// Uses `pipe2(2)` with `O_NONBLOCK | O_CLOEXEC` where available, falling
+/// back to `pipe(2)` plus `O_NONBLOCK`/`FD_CLOEXEC` via `fcntl(2)` otherwise.
+/// The writing end's [`poll_shutdown`][tokio::io::AsyncWrite::poll_shutdown]
+/// closes the write fd outright (pipes don't support `shutdown(2)`), so a
+/// reader blocked on a read sees EOF as soon as the writer is shut down,
+/// without having to wait for it to be dropped too.
+pub fn pipe() -> Result<(PipeReadHalf, PipeWriteHalf)> {
+  let mut fds: [libc::c_int; 2] = [0; 2];
+
+  #[cfg(any(target_os = "linux", target_os = "android"))]
+  let r = unsafe {
+    libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC)
+  };
+  #[cfg(not(any(target_os = "linux", target_os = "android")))]
+  let r = unsafe { libc::pipe(fds.as_mut_ptr()) };
+
+  if r < 0 {
+    return Err(std::io::Error::last_os_error());
+  }
+
+  let (read_fd, write_fd) = (fds[0], fds[1]);
+
+  #[cfg(not(any(target_os = "linux", target_os = "android")))]
+  unsafe {
+    for fd in [read_fd, write_fd] {
+      let fl_flags = libc::fcntl(fd, libc::F_GETFL);
+      let fd_flags = libc::fcntl(fd, libc::F_GETFD);
+      if fl_flags < 0
+        || fd_flags < 0
+        || libc::fcntl(fd, libc::F_SETFL, fl_flags | libc::O_NONBLOCK) < 0
+        || libc::fcntl(fd, libc::F_SETFD, fd_flags | libc::FD_CLOEXEC) < 0
+      {
+        let err = std::io::Error::last_os_error();
+        libc::close(read_fd);
+        libc::close(write_fd);
+        return Err(err);
+      }
+    }
+  }
+
+  let reader = unsafe { PipeReader::from_raw_fd(read_fd) };
+  let writer = unsafe { PipeWriter::from_raw_fd(write_fd) };
+
+  Ok((
+    PipeReadHalf(Anyfd { afd: Some(AsyncFd::new(reader)?), shutdown: ShutdownMode::default() }),
+    PipeWriteHalf(Anyfd { afd: Some(AsyncFd::new(writer)?), shutdown: ShutdownMode::Close }),
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+  #[tokio::test]
+  async fn write_then_read_round_trip() {
+    let (mut reader, mut writer) = pipe().unwrap();
+    writer.write_all(b"hello").await.unwrap();
+    writer.shutdown().await.unwrap();
+
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf).await.unwrap();
+    assert_eq!(buf, b"hello");
+  }
+}
@@ -0,0 +1,172 @@
+use std::future::poll_fn;
+use std::io::{Error, IoSlice, Result};
+use std::mem::{size_of, size_of_val};
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+use futures::ready;
+
+use crate::Anyfd;
+
+/// Linux's `SCM_MAX_FD`: the most file descriptors the kernel will let us
+/// pass in a single `SCM_RIGHTS` message.
+const MAX_FDS: usize = 253;
+
+impl<T: AsRawFd> Anyfd<T> {
+  /// Send `data` over this Unix-domain socket, passing `fds` along as
+  /// ancillary data (`SCM_RIGHTS`).
+  pub async fn send_with_fds(&self, data: &[u8], fds: &[RawFd]) -> Result<usize> {
+    let fd = self.fd().as_raw_fd();
+    poll_fn(|cx| {
+      loop {
+        let mut guard = ready!(self.fd().poll_write_ready(cx))?;
+
+        match guard.try_io(|_| unsafe { send_with_fds(fd, data, fds) }) {
+          Ok(result) => return std::task::Poll::Ready(result),
+          Err(_would_block) => continue,
+        }
+      }
+    }).await
+  }
+
+  /// Receive data over this Unix-domain socket, appending any file
+  /// descriptors passed as ancillary data (`SCM_RIGHTS`) to `fds`, each
+  /// already marked `FD_CLOEXEC`. On Linux and Android this happens
+  /// atomically with the receive, so they can't leak across a concurrent
+  /// `fork`+`exec`; on other platforms it is done with `fcntl` right after
+  /// the receive, which leaves a short window for such a leak.
+  ///
+  /// Returns an error if the kernel reports the ancillary data was
+  /// truncated (`MSG_CTRUNC`), since the truncated fds would otherwise be
+  /// silently leaked.
+  pub async fn recv_with_fds(&self, buf: &mut [u8], fds: &mut Vec<OwnedFd>) -> Result<usize> {
+    let fd = self.fd().as_raw_fd();
+    poll_fn(|cx| {
+      loop {
+        let mut guard = ready!(self.fd().poll_read_ready(cx))?;
+
+        match guard.try_io(|_| unsafe { recv_with_fds(fd, buf, fds) }) {
+          Ok(result) => return std::task::Poll::Ready(result),
+          Err(_would_block) => continue,
+        }
+      }
+    }).await
+  }
+}
+
+unsafe fn send_with_fds(fd: RawFd, data: &[u8], fds: &[RawFd]) -> Result<usize> {
+  let mut iov = [IoSlice::new(data)];
+
+  let mut msg: libc::msghdr = std::mem::zeroed();
+  msg.msg_iov = iov.as_mut_ptr() as *mut libc::iovec;
+  msg.msg_iovlen = iov.len() as _;
+
+  let cmsg_space = libc::CMSG_SPACE(size_of_val(fds) as u32) as usize;
+  let mut cmsg_buf = vec![0u8; cmsg_space];
+
+  if !fds.is_empty() {
+    msg.msg_control = cmsg_buf.as_mut_ptr() as *mut _;
+    msg.msg_controllen = cmsg_space as _;
+
+    let cmsg = libc::CMSG_FIRSTHDR(&msg);
+    (*cmsg).cmsg_level = libc::SOL_SOCKET;
+    (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+    (*cmsg).cmsg_len = libc::CMSG_LEN(size_of_val(fds) as u32) as _;
+
+    std::ptr::copy_nonoverlapping(
+      fds.as_ptr(),
+      libc::CMSG_DATA(cmsg) as *mut RawFd,
+      fds.len(),
+    );
+  }
+
+  let r = libc::sendmsg(fd, &msg, 0);
+  if r < 0 {
+    Err(Error::last_os_error())
+  } else {
+    Ok(r as usize)
+  }
+}
+
+unsafe fn recv_with_fds(fd: RawFd, buf: &mut [u8], out_fds: &mut Vec<OwnedFd>) -> Result<usize> {
+  let mut iov = [libc::iovec {
+    iov_base: buf.as_mut_ptr() as *mut _,
+    iov_len: buf.len(),
+  }];
+
+  let cmsg_space = libc::CMSG_SPACE((MAX_FDS * size_of::<RawFd>()) as u32) as usize;
+  let mut cmsg_buf = vec![0u8; cmsg_space];
+
+  let mut msg: libc::msghdr = std::mem::zeroed();
+  msg.msg_iov = iov.as_mut_ptr();
+  msg.msg_iovlen = iov.len() as _;
+  msg.msg_control = cmsg_buf.as_mut_ptr() as *mut _;
+  msg.msg_controllen = cmsg_space as _;
+
+  // `MSG_CMSG_CLOEXEC` asks the kernel to mark received fds `FD_CLOEXEC`
+  // atomically with `recvmsg(2)`, closing the window a separate `fcntl`
+  // call afterward would leave open for a concurrent `fork`+`exec` to
+  // leak them across. It's Linux-specific, so other platforms fall back
+  // to `fcntl` right after receiving the fds below.
+  #[cfg(any(target_os = "linux", target_os = "android"))]
+  let recvmsg_flags = libc::MSG_CMSG_CLOEXEC;
+  #[cfg(not(any(target_os = "linux", target_os = "android")))]
+  let recvmsg_flags = 0;
+
+  let r = libc::recvmsg(fd, &mut msg, recvmsg_flags);
+  if r < 0 {
+    return Err(Error::last_os_error());
+  }
+
+  // Walk the control messages and claim any fds the kernel already put in
+  // the buffer *before* checking `MSG_CTRUNC`, so a truncated message
+  // still gets the fds it did receive wrapped in an `OwnedFd` (and thus
+  // closed on drop) instead of leaked.
+  let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+  while !cmsg.is_null() {
+    if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+      let data = libc::CMSG_DATA(cmsg);
+      let payload_len = (*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize;
+      let n = payload_len / size_of::<RawFd>();
+      for i in 0..n {
+        let raw: RawFd = std::ptr::read_unaligned((data as *const RawFd).add(i));
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        libc::fcntl(raw, libc::F_SETFD, libc::FD_CLOEXEC);
+        out_fds.push(OwnedFd::from_raw_fd(raw));
+      }
+    }
+    cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+  }
+
+  if msg.msg_flags & libc::MSG_CTRUNC != 0 {
+    return Err(Error::other(
+      "ancillary data truncated (MSG_CTRUNC); refusing to silently drop received fds",
+    ));
+  }
+
+  Ok(r as usize)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::os::unix::net::UnixDatagram;
+
+  #[tokio::test]
+  async fn send_and_recv_fds_round_trip() {
+    let (a, b) = UnixDatagram::pair().unwrap();
+    a.set_nonblocking(true).unwrap();
+    b.set_nonblocking(true).unwrap();
+    let sender = Anyfd::from_fd(a).unwrap();
+    let receiver = Anyfd::from_fd(b).unwrap();
+
+    let passed = std::fs::File::open("/dev/null").unwrap();
+    sender.send_with_fds(b"hello", &[passed.as_raw_fd()]).await.unwrap();
+
+    let mut buf = [0u8; 5];
+    let mut fds = Vec::new();
+    receiver.recv_with_fds(&mut buf, &mut fds).await.unwrap();
+
+    assert_eq!(&buf, b"hello");
+    assert_eq!(fds.len(), 1);
+  }
+}
@@ -0,0 +1,230 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{Error as IoError, Result};
+use std::os::unix::io::AsRawFd;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::ready;
+
+use tokio::io::unix::AsyncFd;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::{Anyfd, ShutdownMode};
+
+/// The reading half of an [`Anyfd`] split by [`Anyfd::into_split`].
+///
+/// Only [`AsyncRead`] is implemented on this half; the underlying fd is
+/// never written to through it.
+pub struct OwnedReadHalf<T: AsRawFd> {
+  afd: Arc<AsyncFd<T>>,
+}
+
+/// The writing half of an [`Anyfd`] split by [`Anyfd::into_split`].
+///
+/// Only [`AsyncWrite`] is implemented on this half; the underlying fd is
+/// never read from through it.
+pub struct OwnedWriteHalf<T: AsRawFd> {
+  afd: Arc<AsyncFd<T>>,
+  shutdown: ShutdownMode,
+}
+
+pub(crate) fn split<T: AsRawFd>(
+  afd: AsyncFd<T>,
+  shutdown: ShutdownMode,
+) -> (OwnedReadHalf<T>, OwnedWriteHalf<T>) {
+  let afd = Arc::new(afd);
+  (OwnedReadHalf { afd: afd.clone() }, OwnedWriteHalf { afd, shutdown })
+}
+
+/// Error returned by [`OwnedReadHalf::reunite`] and
+/// [`OwnedWriteHalf::reunite`] when the two halves did not come from the
+/// same [`Anyfd::into_split`] call.
+pub struct ReuniteError<T: AsRawFd>(pub OwnedReadHalf<T>, pub OwnedWriteHalf<T>);
+
+impl<T: AsRawFd> fmt::Debug for ReuniteError<T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_tuple("ReuniteError").finish()
+  }
+}
+
+impl<T: AsRawFd> fmt::Display for ReuniteError<T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "tried to reunite halves that are not from the same `Anyfd`")
+  }
+}
+
+impl<T: AsRawFd> Error for ReuniteError<T> {}
+
+pub(crate) fn reunite<T: AsRawFd>(
+  read: OwnedReadHalf<T>,
+  write: OwnedWriteHalf<T>,
+) -> std::result::Result<Anyfd<T>, ReuniteError<T>> {
+  if Arc::ptr_eq(&read.afd, &write.afd) {
+    let shutdown = write.shutdown;
+    drop(write.afd);
+    Ok(Anyfd {
+      afd: Some(Arc::try_unwrap(read.afd).ok().expect("`Anyfd::reunite`: only one Arc reference should remain")),
+      shutdown,
+    })
+  } else {
+    Err(ReuniteError(read, write))
+  }
+}
+
+impl<T: AsRawFd> OwnedReadHalf<T> {
+  /// Reunite this half with the corresponding [`OwnedWriteHalf`] to
+  /// recover the original [`Anyfd`], if they were split from the same one.
+  pub fn reunite(self, other: OwnedWriteHalf<T>) -> std::result::Result<Anyfd<T>, ReuniteError<T>> {
+    reunite(self, other)
+  }
+}
+
+impl<T: AsRawFd> OwnedWriteHalf<T> {
+  /// Reunite this half with the corresponding [`OwnedReadHalf`] to
+  /// recover the original [`Anyfd`], if they were split from the same one.
+  pub fn reunite(self, other: OwnedReadHalf<T>) -> std::result::Result<Anyfd<T>, ReuniteError<T>> {
+    reunite(other, self)
+  }
+}
+
+impl<T: AsRawFd> AsyncRead for OwnedReadHalf<T> {
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut ReadBuf<'_>
+  ) -> Poll<Result<()>> {
+    let fd = self.afd.as_raw_fd();
+    loop {
+      let mut guard = ready!(self.afd.poll_read_ready(cx))?;
+
+      match guard.try_io(|_| {
+        let r = unsafe {
+          let unfilled = buf.unfilled_mut();
+          libc::read(fd, unfilled.as_ptr() as *mut _, unfilled.len())
+        };
+        if r < 0 {
+          let err = IoError::last_os_error();
+          Err(err)
+        } else {
+          unsafe { buf.assume_init(r as usize) };
+          buf.advance(r as usize);
+          Ok(())
+        }
+      }) {
+        Ok(result) => return Poll::Ready(result),
+        Err(_would_block) => continue,
+      }
+    }
+  }
+}
+
+impl<T: AsRawFd> AsyncWrite for OwnedWriteHalf<T> {
+  fn poll_write(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &[u8]
+  ) -> Poll<Result<usize>> {
+    let fd = self.afd.as_raw_fd();
+    loop {
+      let mut guard = ready!(self.afd.poll_write_ready(cx))?;
+
+      match guard.try_io(|_| {
+        let r = unsafe {
+          libc::write(fd, buf.as_ptr() as *const _, buf.len())
+        };
+        if r < 0 {
+          let err = IoError::last_os_error();
+          Err(err)
+        } else {
+          Ok(r as usize)
+        }
+      }) {
+        Ok(result) => return Poll::Ready(result),
+        Err(_would_block) => continue,
+      }
+    }
+  }
+
+  fn poll_flush(
+    self: Pin<&mut Self>,
+    _cx: &mut Context<'_>,
+  ) -> Poll<Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+
+  fn poll_shutdown(
+    self: Pin<&mut Self>,
+    _cx: &mut Context<'_>,
+  ) -> Poll<Result<()>> {
+    match self.shutdown {
+      ShutdownMode::Socket(how) => {
+        let how = match how {
+          std::net::Shutdown::Read => libc::SHUT_RD,
+          std::net::Shutdown::Write => libc::SHUT_WR,
+          std::net::Shutdown::Both => libc::SHUT_RDWR,
+        };
+        let fd = self.afd.as_raw_fd();
+        let r = unsafe {
+          libc::shutdown(fd, how)
+        };
+        if r == 0 {
+          Poll::Ready(Ok(()))
+        } else {
+          let err = IoError::last_os_error();
+          if err.raw_os_error() == Some(libc::ENOTSOCK) {
+            Poll::Ready(Ok(()))
+          } else {
+            Poll::Ready(Err(err))
+          }
+        }
+      }
+      // The fd here is shared with `OwnedReadHalf` via `Arc`, so unlike
+      // the unsplit `Anyfd`, this half can't safely take and close it
+      // out from under the other half; `Close` degrades to `None`.
+      ShutdownMode::Close | ShutdownMode::None => Poll::Ready(Ok(())),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::os::unix::net::UnixStream;
+
+  use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+  use crate::Anyfd;
+
+  #[tokio::test]
+  async fn concurrent_read_write_then_reunite() {
+    let (a, b) = UnixStream::pair().unwrap();
+    let mut peer = Anyfd::from_fd(b).unwrap();
+    let (mut read_half, mut write_half) = Anyfd::from_fd(a).unwrap().into_split();
+
+    let peer_task = tokio::spawn(async move {
+      let mut buf = [0u8; 4];
+      peer.read_exact(&mut buf).await.unwrap();
+      peer.write_all(&buf).await.unwrap();
+    });
+
+    let mut buf = [0u8; 4];
+    let write_fut = write_half.write_all(b"ping");
+    let read_fut = read_half.read_exact(&mut buf);
+    tokio::try_join!(write_fut, read_fut).unwrap();
+    peer_task.await.unwrap();
+
+    assert_eq!(&buf, b"ping");
+    read_half.reunite(write_half).unwrap();
+  }
+
+  #[tokio::test]
+  async fn reunite_mismatched_halves_errors() {
+    let (a1, _b1) = UnixStream::pair().unwrap();
+    let (a2, _b2) = UnixStream::pair().unwrap();
+    let (read1, _write1) = Anyfd::from_fd(a1).unwrap().into_split();
+    let (_read2, write2) = Anyfd::from_fd(a2).unwrap().into_split();
+
+    assert!(read1.reunite(write2).is_err());
+  }
+}